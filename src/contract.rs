@@ -3,12 +3,17 @@
 #![allow(clippy::needless_pass_by_value)]
 
 use cosmwasm_std::{
-    generic_err, to_binary, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier,
+    to_binary, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier, StdError,
     StdResult, Storage,
 };
 
-use crate::msg::{Credentials, HandleMsg, InitMsg, QueryMsg};
-use crate::state::{Coords, Game, Pasture, Player};
+use sha2::{Digest, Sha256};
+
+use crate::msg::{Credentials, GameStatusResponse, HandleMsg, InitMsg, QueryMsg};
+use crate::state::{
+    commitment_digest, hash_password, Coords, Game, GameStatus, Herd, Orientation, Pasture, Player,
+    BOARD_SIZE, FLEET_CELLS, FLEET_LENGTHS,
+};
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     _deps: &mut Extern<S, A, Q>,
@@ -20,65 +25,309 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
 
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
+    env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
     match msg {
-        HandleMsg::NewGame { name } => try_new_game(&mut deps.storage, name),
-        HandleMsg::Join {
+        HandleMsg::NewGame {
+            name,
+            turn_timeout_seconds,
+        } => try_new_game(&mut deps.storage, env, name, turn_timeout_seconds),
+        HandleMsg::NewSoloGame {
+            name,
+            credentials,
+            pasture,
+            turn_timeout_seconds,
+        } => try_new_solo_game(
+            &mut deps.storage,
+            env,
+            name,
+            credentials,
             pasture,
+            turn_timeout_seconds,
+        ),
+        HandleMsg::Join {
+            commitment,
             credentials,
-        } => try_join(&mut deps.storage, credentials, pasture),
+        } => try_join(&mut deps.storage, env, credentials, commitment),
         HandleMsg::Shoot {
             coords,
             credentials,
-        } => try_shoot(&mut deps.storage, credentials, coords),
+        } => try_shoot(&mut deps.storage, env, credentials, coords),
         HandleMsg::Confirm {
             coords,
             credentials,
-        } => try_confirm(&mut deps.storage, credentials, coords),
+            hit,
+        } => try_confirm(&mut deps.storage, env, credentials, coords, hit),
+        HandleMsg::Reveal {
+            credentials,
+            pasture,
+            salt,
+        } => try_reveal(&mut deps.storage, credentials, pasture, salt),
+        HandleMsg::ClaimTimeout { credentials } => {
+            try_claim_timeout(&mut deps.storage, env, credentials)
+        }
     }
 }
 
-fn try_new_game<S: Storage>(storage: &mut S, name: String) -> StdResult<HandleResponse> {
+fn try_new_game<S: Storage>(
+    storage: &mut S,
+    env: Env,
+    name: String,
+    turn_timeout_seconds: Option<u64>,
+) -> StdResult<HandleResponse> {
     // As long as the storage isn't corrupted somehow, this `?` should always succeed.
     if Game::may_load(storage, name.clone())?.is_some() {
-        return Err(generic_err(format!(
-            "game with name {:?} already exists",
-            name
+        return Err(StdError::generic_err(format!(
+            "game with name {name:?} already exists"
         )));
     }
+    if turn_timeout_seconds == Some(0) {
+        return Err(StdError::generic_err(
+            "turn_timeout_seconds must be positive, or omitted to disable the timeout",
+        ));
+    }
 
-    Game::new(name).save(storage)?;
+    Game::new(name, turn_timeout_seconds, env.block.time).save(storage)?;
 
     Ok(HandleResponse::default())
 }
 
-fn try_join<S: Storage>(
+fn try_new_solo_game<S: Storage>(
     storage: &mut S,
+    env: Env,
+    name: String,
     credentials: Credentials,
     pasture: Pasture,
+    turn_timeout_seconds: Option<u64>,
+) -> StdResult<HandleResponse> {
+    if Game::may_load(storage, name.clone())?.is_some() {
+        return Err(StdError::generic_err(format!(
+            "game with name {name:?} already exists"
+        )));
+    }
+    if turn_timeout_seconds == Some(0) {
+        return Err(StdError::generic_err(
+            "turn_timeout_seconds must be positive, or omitted to disable the timeout",
+        ));
+    }
+
+    pasture.validate()?;
+
+    let password_salt = generate_salt(&env, &name, &credentials.username);
+    let password_hash = hash_password(&credentials.password, &password_salt);
+
+    let mut game = Game::new(name, turn_timeout_seconds, env.block.time);
+    game.add_player(Player::new_known(
+        credentials.username,
+        password_hash,
+        password_salt,
+        pasture,
+    ))?;
+    game.add_player(Player::new_ai("computer".to_string(), ai_pasture(&env, &game.name)))?;
+
+    game.save(storage)?;
+
+    Ok(HandleResponse::default())
+}
+
+/// The fleet placement stops retrying a herd after this many random
+/// attempts and falls back to a deterministic scan instead; with only 17
+/// cells to place on a 100-cell board this should essentially never
+/// trigger, but it keeps placement from looping forever.
+const MAX_PLACEMENT_ATTEMPTS: u32 = 200;
+
+/// A byte stream derived by repeatedly re-hashing a seed with a counter,
+/// since `sha2` is the only hashing primitive available to this contract
+/// and there is no `rand` dependency (or any source of on-chain entropy
+/// at all) to draw from instead.
+struct HashStream {
+    seed: Vec<u8>,
+    counter: u64,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl HashStream {
+    fn new(seed: Vec<u8>) -> Self {
+        HashStream {
+            seed,
+            counter: 0,
+            block: vec![],
+            pos: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        if self.pos >= self.block.len() {
+            let mut hasher = Sha256::new();
+            hasher.update(&self.seed);
+            hasher.update(self.counter.to_le_bytes());
+            self.block = hasher.finalize().to_vec();
+            self.counter += 1;
+            self.pos = 0;
+        }
+        let byte = self.block[self.pos];
+        self.pos += 1;
+        byte
+    }
+
+    /// A value in `0..bound`. Slightly biased towards the low end for
+    /// `bound`s that don't divide 256, which is irrelevant here: `bound`
+    /// never exceeds `BOARD_SIZE`.
+    fn next_below(&mut self, bound: u8) -> u8 {
+        self.next_byte() % bound
+    }
+}
+
+/// Whether `candidate` shares a cell with any herd already placed.
+fn overlaps(placed: &[Herd], candidate: &Herd) -> bool {
+    let cells = candidate.cells();
+    placed
+        .iter()
+        .any(|herd| herd.cells().iter().any(|cell| cells.contains(cell)))
+}
+
+/// Deterministically finds the first legal cell for a herd of `length`,
+/// scanning row-major in each orientation. Only reached if
+/// `MAX_PLACEMENT_ATTEMPTS` random tries all land on an occupied cell.
+fn fallback_herd(placed: &[Herd], length: u8) -> Herd {
+    for orientation in [Orientation::Horizontal, Orientation::Vertical] {
+        let (max_x, max_y) = match orientation {
+            Orientation::Horizontal => (BOARD_SIZE - length, BOARD_SIZE - 1),
+            Orientation::Vertical => (BOARD_SIZE - 1, BOARD_SIZE - length),
+        };
+        for y in 0..=max_y {
+            for x in 0..=max_x {
+                let herd = Herd::new(x, y, length, orientation);
+                if !overlaps(placed, &herd) {
+                    return herd;
+                }
+            }
+        }
+    }
+    // The full fleet is only 17 cells on a 100-cell board, so there is
+    // always free space left for whatever has been placed so far.
+    unreachable!("no legal cell left for a length-{} herd", length)
+}
+
+/// Procedurally places the AI's own fleet from a hash-derived byte stream,
+/// rather than picking from a small set of known boards a player could
+/// memorize outright. This only widens the space of possible boards; it
+/// does not make the AI's fleet secret. Every input to the seed (the
+/// player's own address, the block height and time the game lands in, and
+/// the game name) is public on-chain data the player can read back before
+/// firing a single shot, and the placement algorithm is public source, so
+/// a determined player can still recompute the exact fleet. Closing that
+/// gap for real would need a verifiable randomness source this contract
+/// and chain don't have; until then, the AI fleet's secrecy against a
+/// player willing to script around it is out of scope.
+fn ai_pasture(env: &Env, game_name: &str) -> Pasture {
+    let mut seed = Vec::new();
+    seed.extend_from_slice(env.message.sender.as_str().as_bytes());
+    seed.extend_from_slice(&env.block.height.to_le_bytes());
+    seed.extend_from_slice(&env.block.time.to_le_bytes());
+    seed.extend_from_slice(game_name.as_bytes());
+    seed.extend_from_slice(b"ai-pasture");
+    let mut rng = HashStream::new(seed);
+
+    let mut herds: Vec<Herd> = Vec::with_capacity(FLEET_LENGTHS.len());
+    // Largest herds first: they have the fewest legal cells, so placing
+    // them while the board is still empty keeps the retry loop short.
+    for &length in FLEET_LENGTHS.iter().rev() {
+        let mut placed = None;
+        for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+            let orientation = if rng.next_below(2) == 0 {
+                Orientation::Horizontal
+            } else {
+                Orientation::Vertical
+            };
+            let (max_x, max_y) = match orientation {
+                Orientation::Horizontal => (BOARD_SIZE - length, BOARD_SIZE - 1),
+                Orientation::Vertical => (BOARD_SIZE - 1, BOARD_SIZE - length),
+            };
+            let herd = Herd::new(
+                rng.next_below(max_x + 1),
+                rng.next_below(max_y + 1),
+                length,
+                orientation,
+            );
+            if !overlaps(&herds, &herd) {
+                placed = Some(herd);
+                break;
+            }
+        }
+        herds.push(placed.unwrap_or_else(|| fallback_herd(&herds, length)));
+    }
+
+    Pasture::new(herds, vec![])
+}
+
+fn try_join<S: Storage>(
+    storage: &mut S,
+    env: Env,
+    credentials: Credentials,
+    commitment: Binary,
 ) -> StdResult<HandleResponse> {
     let mut game = Game::load(storage, credentials.game.clone())?;
-    let player = Player::new(credentials.username, credentials.password, pasture);
+
+    let password_salt = generate_salt(&env, &credentials.game, &credentials.username);
+    let password_hash = hash_password(&credentials.password, &password_salt);
+    let player = Player::new(
+        credentials.username,
+        password_hash,
+        password_salt,
+        commitment,
+    );
     game.add_player(player)?;
+    game.touch(env.block.time);
 
     game.save(storage)?;
 
     Ok(HandleResponse::default())
 }
 
+/// Derives a per-player salt from data no one controls end-to-end: the
+/// sender address, the current block, and the game and username being
+/// registered. The game name is part of the hash so that two games joined
+/// (or created) by the same sender under the same username in the same
+/// block still get distinct salts.
+fn generate_salt(env: &Env, game: &str, username: &str) -> Binary {
+    let mut hasher = Sha256::new();
+    hasher.update(env.message.sender.as_str().as_bytes());
+    hasher.update(env.block.height.to_le_bytes());
+    hasher.update(env.block.time.to_le_bytes());
+    hasher.update(game.as_bytes());
+    hasher.update(username.as_bytes());
+    Binary::from(hasher.finalize().to_vec())
+}
+
 fn try_shoot<S: Storage>(
     storage: &mut S,
+    env: Env,
     credentials: Credentials,
     coords: Coords,
 ) -> StdResult<HandleResponse> {
     let mut game = Game::load(storage, credentials.game.clone())?.full()?;
+    game.ensure_in_progress()?;
 
-    if game.player().matches_credentials(&credentials) {
-        return Err(generic_err("It's not your turn".to_string()));
+    if !game.opponent().matches_credentials(&credentials) {
+        return Err(StdError::generic_err("It's not your turn".to_string()));
+    }
+    if game.already_shot(coords) {
+        return Err(StdError::generic_err("that cell has already been shot at"));
     }
     game.shoot(coords);
+    game.touch(env.block.time);
+
+    // The defender's board may already be known to the contract (a solo
+    // game's human seat, or the AI opponent) rather than hidden behind a
+    // commitment, in which case the shot can be confirmed right away.
+    if let Some(hit) = game.known_hit(coords) {
+        game.confirm_shot(coords, hit, true);
+        game.end_turn();
+        game.take_ai_turn_if_due();
+    }
 
     game.save(storage)?;
 
@@ -87,18 +336,103 @@ fn try_shoot<S: Storage>(
 
 fn try_confirm<S: Storage>(
     storage: &mut S,
+    env: Env,
     credentials: Credentials,
     coords: Coords,
+    hit: bool,
 ) -> StdResult<HandleResponse> {
     let mut game = Game::load(storage, credentials.game.clone())?.full()?;
+    game.ensure_in_progress()?;
 
-    if game.opponent().matches_credentials(&credentials) {
-        return Err(generic_err(
+    if !game.player().matches_credentials(&credentials) {
+        return Err(StdError::generic_err(
             "You do not have permissions to confirm this shot".to_string(),
         ));
     }
-    game.confirm_shot(coords);
+    // `hit` is only the defender's word for it here; it can't end the game
+    // until it survives the defender's eventual `Reveal` (see try_reveal).
+    game.confirm_shot(coords, hit, false);
     game.end_turn();
+    game.touch(env.block.time);
+
+    game.save(storage)?;
+
+    Ok(HandleResponse::default())
+}
+
+fn try_reveal<S: Storage>(
+    storage: &mut S,
+    credentials: Credentials,
+    pasture: Pasture,
+    salt: Binary,
+) -> StdResult<HandleResponse> {
+    let mut game = Game::load(storage, credentials.game.clone())?.full()?;
+
+    let index = game
+        .players
+        .iter()
+        .position(|p| p.matches_credentials(&credentials))
+        .ok_or_else(|| StdError::generic_err("You do not have permissions to reveal this pasture"))?;
+
+    if commitment_digest(&pasture, &salt)? != game.players[index].commitment {
+        return Err(StdError::generic_err(
+            "revealed pasture and salt do not match the committed board",
+        ));
+    }
+
+    let opponent = game.players[1 - index].username.clone();
+
+    // From here on the revealed board is this player's word made binding:
+    // if it turns out to be illegal, or to contradict a shot they already
+    // confirmed, that's proof of cheating and forfeits the game outright,
+    // rather than merely erroring with no consequence (a transaction error
+    // reverts all state, so it could never record the forfeit itself).
+    // Guarded by `is_finished`, same as the fleet-sunk check below, so a
+    // reveal can't overturn a match already decided by other means (e.g. a
+    // timeout claim or the other player's own reveal).
+    if pasture.validate().is_err() {
+        if !game.is_finished() {
+            game.status = GameStatus::Finished { winner: opponent };
+        }
+        game.save(storage)?;
+        return Ok(HandleResponse::default());
+    }
+
+    let lied = game
+        .confirmed_shots
+        .iter()
+        .filter(|shot| shot.target as usize == index)
+        .any(|shot| pasture.is_hit(shot.coords) != shot.hit);
+
+    if lied {
+        if !game.is_finished() {
+            game.status = GameStatus::Finished { winner: opponent };
+        }
+        game.save(storage)?;
+        return Ok(HandleResponse::default());
+    }
+
+    game.players[index].revealed_pasture = Some(pasture);
+
+    // Every confirmed hit against this board has now checked out, so a
+    // fleet reported sunk during play can finally be trusted.
+    if !game.is_finished() && game.players[index].hits_taken >= FLEET_CELLS {
+        game.status = GameStatus::Finished { winner: opponent };
+    }
+
+    game.save(storage)?;
+
+    Ok(HandleResponse::default())
+}
+
+fn try_claim_timeout<S: Storage>(
+    storage: &mut S,
+    env: Env,
+    credentials: Credentials,
+) -> StdResult<HandleResponse> {
+    let mut game = Game::load(storage, credentials.game.clone())?.full()?;
+
+    game.claim_timeout(&credentials, env.block.time)?;
 
     game.save(storage)?;
 
@@ -113,6 +447,7 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
         QueryMsg::MyPasture { credentials } => try_get_my_pasture(&deps.storage, credentials),
         QueryMsg::MyShots { credentials } => try_get_my_shots(&deps.storage, credentials),
         QueryMsg::LastShot { credentials } => try_get_last_shot(&deps.storage, credentials),
+        QueryMsg::GameStatus { credentials } => try_get_game_status(&deps.storage, credentials),
     }
 }
 
@@ -122,7 +457,8 @@ fn try_get_my_pasture<S: Storage>(storage: &S, credentials: Credentials) -> StdR
     let pasture = game
         .player()
         .pasture(&credentials)
-        .ok_or_else(|| generic_err("You do not have permissions to get the shots".to_string()))?;
+        .or_else(|| game.opponent().pasture(&credentials))
+        .ok_or_else(|| StdError::generic_err("this pasture has not been revealed yet".to_string()))?;
 
     to_binary(pasture)
 }
@@ -136,7 +472,7 @@ pub fn try_get_my_shots<S: Storage>(storage: &S, credentials: Credentials) -> St
     } else if opponent.matches_credentials(&credentials) {
         game.get_opponent_shots()
     } else {
-        return Err(generic_err(
+        return Err(StdError::generic_err(
             "You do not have permissions to get this information".to_string(),
         ));
     };
@@ -144,6 +480,35 @@ pub fn try_get_my_shots<S: Storage>(storage: &S, credentials: Credentials) -> St
     to_binary(&shots)
 }
 
+pub fn try_get_game_status<S: Storage>(storage: &S, credentials: Credentials) -> StdResult<Binary> {
+    let game = Game::load(storage, credentials.game.clone())?.full()?;
+    let player = game.player();
+    let opponent = game.opponent();
+
+    let (you, them) = if player.matches_credentials(&credentials) {
+        (player, opponent)
+    } else if opponent.matches_credentials(&credentials) {
+        (opponent, player)
+    } else {
+        return Err(StdError::generic_err(
+            "You do not have permissions to get this information".to_string(),
+        ));
+    };
+
+    let (finished, winner) = match &game.status {
+        GameStatus::InProgress => (false, None),
+        GameStatus::Finished { winner } => (true, Some(winner.clone())),
+    };
+
+    to_binary(&GameStatusResponse {
+        turn_username: game.next_actor().username.clone(),
+        your_losses: you.hits_taken,
+        opponent_losses: them.hits_taken,
+        finished,
+        winner,
+    })
+}
+
 pub fn try_get_last_shot<S: Storage>(storage: &S, credentials: Credentials) -> StdResult<Binary> {
     let game = Game::load(storage, credentials.game.clone())?.full()?;
     let player = game.player();
@@ -152,7 +517,7 @@ pub fn try_get_last_shot<S: Storage>(storage: &S, credentials: Credentials) -> S
         if player.matches_credentials(&credentials) || opponent.matches_credentials(&credentials) {
             game.next_shot()
         } else {
-            return Err(generic_err(
+            return Err(StdError::generic_err(
                 "You do not have permissions to get this information".to_string(),
             ));
         };
@@ -165,30 +530,561 @@ pub fn try_get_last_shot<S: Storage>(storage: &S, credentials: Credentials) -> S
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::{coins, StdError, HandleResult, from_binary};
-    use crate::state::{Orientation, Herd, Pasture};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coins, from_binary, HandleResult, Binary};
+    use crate::state::{commitment_digest, Orientation, Herd, Pasture};
+
+    /// A validated 5-herd fleet; used where tests just need *some* legal
+    /// board rather than a specific layout.
+    fn sample_pasture_a() -> Pasture {
+        Pasture::new(
+            vec![
+                Herd::new(0, 0, 2, Orientation::Horizontal),
+                Herd::new(3, 2, 3, Orientation::Horizontal),
+                Herd::new(4, 6, 3, Orientation::Vertical),
+                Herd::new(6, 0, 4, Orientation::Horizontal),
+                Herd::new(2, 2, 5, Orientation::Vertical),
+            ],
+            vec![],
+        )
+    }
+
+    fn sample_pasture_b() -> Pasture {
+        Pasture::new(
+            vec![
+                Herd::new(6, 5, 2, Orientation::Vertical),
+                Herd::new(1, 4, 3, Orientation::Horizontal),
+                Herd::new(4, 4, 3, Orientation::Horizontal),
+                Herd::new(8, 3, 4, Orientation::Vertical),
+                Herd::new(5, 9, 5, Orientation::Horizontal),
+            ],
+            vec![],
+        )
+    }
+
+    /// Creates a game and joins it with `alice` (who ends up as the
+    /// attacker, having joined first) and `bob` (the defender), each
+    /// committed to one of the two sample pastures above. Returns both
+    /// players' credentials plus bob's plaintext pasture and salt, since
+    /// the forfeit-on-reveal test needs to reveal them later.
+    fn join_alice_and_bob(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        name: &str,
+    ) -> (Credentials, Credentials, Pasture, Binary) {
+        handle(
+            deps,
+            mock_env("creator", &coins(1000, "token")),
+            HandleMsg::NewGame {
+                name: name.to_string(),
+                turn_timeout_seconds: None,
+            },
+        )
+        .unwrap();
+
+        let alice_salt = Binary::from(b"alice-salt".to_vec());
+        let bob_salt = Binary::from(b"bob-salt".to_vec());
+        let alice_pasture = sample_pasture_a();
+        let bob_pasture = sample_pasture_b();
+
+        let alice_credentials = Credentials {
+            game: name.to_string(),
+            username: "alice".to_string(),
+            password: "alice-pw".to_string(),
+        };
+        let bob_credentials = Credentials {
+            game: name.to_string(),
+            username: "bob".to_string(),
+            password: "bob-pw".to_string(),
+        };
+
+        handle(
+            deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Join {
+                credentials: alice_credentials.clone(),
+                commitment: commitment_digest(&alice_pasture, &alice_salt).unwrap(),
+            },
+        )
+        .unwrap();
+        handle(
+            deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Join {
+                credentials: bob_credentials.clone(),
+                commitment: commitment_digest(&bob_pasture, &bob_salt).unwrap(),
+            },
+        )
+        .unwrap();
+
+        (alice_credentials, bob_credentials, bob_pasture, bob_salt)
+    }
+
+    #[test]
+    fn shoot_and_confirm_require_matching_credentials() {
+        let mut deps = mock_dependencies(20, &[]);
+        let (alice_credentials, bob_credentials, _, _) = join_alice_and_bob(&mut deps, "bar");
+
+        // alice joined first, so she's the attacker and bob is the
+        // defender. bob is a real player in this game, just not the one
+        // whose turn it is to fire.
+        let res: HandleResult = handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: bob_credentials.clone(),
+                coords: Coords { x: 0, y: 0 },
+            },
+        );
+        match res.unwrap_err() {
+            StdError::GenericErr { msg, .. } => assert_eq!(msg, "It's not your turn"),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        // alice, the real attacker, may fire.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials.clone(),
+                coords: Coords { x: 0, y: 0 },
+            },
+        )
+        .unwrap();
+
+        // alice, the attacker, has no standing to confirm her own shot.
+        let res: HandleResult = handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Confirm {
+                credentials: alice_credentials,
+                coords: Coords { x: 0, y: 0 },
+                hit: true,
+            },
+        );
+        match res.unwrap_err() {
+            StdError::GenericErr { msg, .. } => {
+                assert_eq!(msg, "You do not have permissions to confirm this shot");
+            }
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn dishonest_confirmation_forfeits_the_game_on_reveal() {
+        let mut deps = mock_dependencies(20, &[]);
+        let (alice_credentials, bob_credentials, bob_pasture, bob_salt) =
+            join_alice_and_bob(&mut deps, "baz");
+
+        // alice (the attacker) fires on a cell that is actually one of
+        // bob's herds.
+        let hit_coords = bob_pasture.herds[0].cells()[0];
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials.clone(),
+                coords: hit_coords,
+            },
+        )
+        .unwrap();
+
+        // bob lies and confirms it as a miss.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Confirm {
+                credentials: bob_credentials.clone(),
+                coords: hit_coords,
+                hit: false,
+            },
+        )
+        .unwrap();
+
+        // The lie hasn't been caught yet, so the game is still in progress.
+        let status: GameStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GameStatus {
+                    credentials: alice_credentials.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(!status.finished);
+
+        // bob reveals his real board, which contradicts the "miss" he
+        // confirmed and forfeits the game to alice.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Reveal {
+                credentials: bob_credentials,
+                pasture: bob_pasture,
+                salt: bob_salt,
+            },
+        )
+        .unwrap();
+
+        let status: GameStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GameStatus {
+                    credentials: alice_credentials,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(status.finished);
+        assert_eq!(status.winner, Some("alice".to_string()));
+    }
+
+    #[test]
+    fn revealing_an_illegal_board_forfeits_the_game() {
+        let mut deps = mock_dependencies(20, &[]);
+        handle(
+            &mut deps,
+            mock_env("creator", &coins(1000, "token")),
+            HandleMsg::NewGame {
+                name: "cheat".to_string(),
+                turn_timeout_seconds: None,
+            },
+        )
+        .unwrap();
+
+        let alice_credentials = Credentials {
+            game: "cheat".to_string(),
+            username: "alice".to_string(),
+            password: "alice-pw".to_string(),
+        };
+        let bob_credentials = Credentials {
+            game: "cheat".to_string(),
+            username: "bob".to_string(),
+            password: "bob-pw".to_string(),
+        };
+
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Join {
+                credentials: alice_credentials.clone(),
+                commitment: commitment_digest(&sample_pasture_a(), &Binary::from(b"a".to_vec()))
+                    .unwrap(),
+            },
+        )
+        .unwrap();
+
+        // bob commits to a board with two overlapping herds: legal enough
+        // to pass at commit time (only a hash is sent), but not a real
+        // fleet layout.
+        let bob_salt = Binary::from(b"bob-salt".to_vec());
+        let overlapping_pasture = Pasture::new(
+            vec![
+                Herd::new(0, 0, 2, Orientation::Horizontal),
+                Herd::new(0, 0, 3, Orientation::Horizontal),
+                Herd::new(0, 0, 3, Orientation::Vertical),
+                Herd::new(6, 0, 4, Orientation::Horizontal),
+                Herd::new(2, 2, 5, Orientation::Vertical),
+            ],
+            vec![],
+        );
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Join {
+                credentials: bob_credentials.clone(),
+                commitment: commitment_digest(&overlapping_pasture, &bob_salt).unwrap(),
+            },
+        )
+        .unwrap();
+
+        // Nothing in the PvP flow ever calls Pasture::validate on bob's
+        // board until he reveals it; revealing it now is the only place
+        // this can be caught, and it must cost him the game rather than
+        // just error out.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Reveal {
+                credentials: bob_credentials,
+                pasture: overlapping_pasture,
+                salt: bob_salt,
+            },
+        )
+        .unwrap();
+
+        let status: GameStatusResponse = from_binary(
+            &query(&deps, QueryMsg::GameStatus { credentials: alice_credentials }).unwrap(),
+        )
+        .unwrap();
+        assert!(status.finished);
+        assert_eq!(status.winner, Some("alice".to_string()));
+    }
+
+    #[test]
+    fn shooting_an_already_shot_cell_is_rejected() {
+        let mut deps = mock_dependencies(20, &[]);
+        let (alice_credentials, bob_credentials, _, _) = join_alice_and_bob(&mut deps, "repeat");
+
+        // alice hits the same cell of bob's fleet that sample_pasture_b
+        // actually occupies, confirms, then bob fires back (so the turn
+        // returns to alice) before alice tries to re-shoot it.
+        let target = Coords { x: 6, y: 5 };
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials.clone(),
+                coords: target,
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Confirm {
+                credentials: bob_credentials.clone(),
+                coords: target,
+                hit: true,
+            },
+        )
+        .unwrap();
+
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: bob_credentials.clone(),
+                coords: Coords { x: 9, y: 9 },
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Confirm {
+                credentials: alice_credentials.clone(),
+                coords: Coords { x: 9, y: 9 },
+                hit: false,
+            },
+        )
+        .unwrap();
+
+        // it's alice's turn again; re-shooting the cell she already hit
+        // must not be allowed to rack up another confirmed hit.
+        let res: HandleResult = handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials,
+                coords: target,
+            },
+        );
+        match res.unwrap_err() {
+            StdError::GenericErr { msg, .. } => {
+                assert_eq!(msg, "that cell has already been shot at");
+            }
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        let mut deps = mock_dependencies(20, &[]);
+        let (alice_credentials, _bob_credentials, _, _) = join_alice_and_bob(&mut deps, "badpw");
+
+        let mut impostor = alice_credentials.clone();
+        impostor.password = "wrong-password".to_string();
+
+        let res: HandleResult = handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: impostor,
+                coords: Coords { x: 0, y: 0 },
+            },
+        );
+        match res.unwrap_err() {
+            StdError::GenericErr { msg, .. } => assert_eq!(msg, "It's not your turn"),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        // alice's real password still works.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials,
+                coords: Coords { x: 0, y: 0 },
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn password_salts_differ_across_games_in_the_same_block() {
+        let mut deps = mock_dependencies(20, &[]);
+        let credentials_one = Credentials {
+            game: "salt-one".to_string(),
+            username: "alice".to_string(),
+            password: "same-password".to_string(),
+        };
+        let credentials_two = Credentials {
+            game: "salt-two".to_string(),
+            username: "alice".to_string(),
+            password: "same-password".to_string(),
+        };
+
+        for credentials in [&credentials_one, &credentials_two] {
+            handle(
+                &mut deps,
+                mock_env("creator", &coins(1000, "token")),
+                HandleMsg::NewGame {
+                    name: credentials.game.clone(),
+                    turn_timeout_seconds: None,
+                },
+            )
+            .unwrap();
+            handle(
+                &mut deps,
+                mock_env("anyone", &coins(2, "token")),
+                HandleMsg::Join {
+                    credentials: credentials.clone(),
+                    commitment: commitment_digest(
+                        &sample_pasture_a(),
+                        &Binary::from(b"s".to_vec()),
+                    )
+                    .unwrap(),
+                },
+            )
+            .unwrap();
+        }
+
+        let game_one = Game::load(&deps.storage, "salt-one".to_string()).unwrap();
+        let game_two = Game::load(&deps.storage, "salt-two".to_string()).unwrap();
+        assert_ne!(
+            game_one.players[0].password_salt,
+            game_two.players[0].password_salt
+        );
+        assert_ne!(
+            game_one.players[0].password_hash,
+            game_two.players[0].password_hash
+        );
+    }
+
+    #[test]
+    fn validate_rejects_zero_length_herds_instead_of_panicking() {
+        let pasture = Pasture::new(vec![Herd::new(0, 0, 0, Orientation::Horizontal)], vec![]);
+        assert!(pasture.validate().is_err());
+    }
+
+    #[test]
+    fn game_status_reports_whoever_currently_owes_a_move() {
+        let mut deps = mock_dependencies(20, &[]);
+        let (alice_credentials, bob_credentials, _, _) = join_alice_and_bob(&mut deps, "turns");
+
+        let get_turn_username = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let status: GameStatusResponse = from_binary(
+                &query(
+                    deps,
+                    QueryMsg::GameStatus {
+                        credentials: alice_credentials.clone(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            status.turn_username
+        };
+
+        // alice joined first, so she's due to fire.
+        assert_eq!(get_turn_username(&deps), "alice");
+
+        // Once she shoots, it's bob (the defender) who owes the next move:
+        // confirming whether it hit.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Shoot {
+                credentials: alice_credentials.clone(),
+                coords: Coords { x: 0, y: 0 },
+            },
+        )
+        .unwrap();
+        assert_eq!(get_turn_username(&deps), "bob");
+
+        // Once bob confirms, the turn passes and it's his turn to fire.
+        handle(
+            &mut deps,
+            mock_env("anyone", &coins(2, "token")),
+            HandleMsg::Confirm {
+                credentials: bob_credentials,
+                coords: Coords { x: 0, y: 0 },
+                hit: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(get_turn_username(&deps), "bob");
+    }
+
+    #[test]
+    fn ai_pasture_is_always_legal_and_not_limited_to_a_couple_of_boards() {
+        use cosmwasm_std::to_vec;
+        use std::collections::HashSet;
+
+        let mut seen = HashSet::new();
+        for height in 0..8u64 {
+            let mut env = mock_env("anyone", &coins(2, "token"));
+            env.block.height = height;
+            let pasture = ai_pasture(&env, "solo-game");
+            pasture.validate().unwrap();
+            seen.insert(to_vec(&pasture).unwrap());
+        }
+
+        // A placement drawn from a couple of fixed boards would show at most
+        // 2 distinct layouts here; 8 different seeds should comfortably
+        // clear that.
+        assert!(
+            seen.len() > 2,
+            "expected more variety than a couple of fixed boards, got {} distinct layouts",
+            seen.len()
+        );
+    }
+
+    #[test]
+    fn ai_pasture_differs_between_games_even_in_the_same_block() {
+        use cosmwasm_std::to_vec;
+
+        // Same sender, same block: only the game name differs. Two solo
+        // games created back-to-back shouldn't get the same AI board.
+        let env = mock_env("anyone", &coins(2, "token"));
+        let pasture_a = ai_pasture(&env, "g1");
+        let pasture_b = ai_pasture(&env, "g2");
+
+        assert_ne!(to_vec(&pasture_a).unwrap(), to_vec(&pasture_b).unwrap());
+    }
 
     #[test]
     fn main_game_flow() {
         let mut deps = mock_dependencies(20, &[]);
 
         let msg = InitMsg { };
-        let env = mock_env(&deps.api, "creator", &coins(1000, "token"));
+        let env = mock_env("creator", &coins(1000, "token"));
 
         // we can just call .unwrap() to assert this was a success
         let res = init(&mut deps, env, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
         // Create new game with "foo" name
-        let env = mock_env(&deps.api, "anyone", &coins(2, "token"));
-        let msg = HandleMsg::NewGame { name: "foo".to_string() };
+        let env = mock_env("anyone", &coins(2, "token"));
+        let msg = HandleMsg::NewGame { name: "foo".to_string(), turn_timeout_seconds: None };
         let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
         // Create new game with "foo" name duplicated - ERROR
-        let env = mock_env(&deps.api, "anyone", &coins(2, "token"));
-        let msg = HandleMsg::NewGame { name: "foo".to_string() };
+        let env = mock_env("anyone", &coins(2, "token"));
+        let msg = HandleMsg::NewGame { name: "foo".to_string(), turn_timeout_seconds: None };
         let res: HandleResult = handle(&mut deps, env, msg);
 
         match res.unwrap_err() {
@@ -216,30 +1112,32 @@ mod tests {
 	    //  4x -> (6,0,Horizontal)
 	    //  5x -> (2,2,Vertical)
 
-        let msg = HandleMsg::Join { 
+        let player1_salt = Binary::from(b"player1-salt".to_vec());
+        let player1_pasture = Pasture::new(
+            vec![
+                // 2x Length Herds
+                Herd::new(0, 0, 2, Orientation::Horizontal),
+                // 3x Length Herds
+                Herd::new(3, 2, 3, Orientation::Horizontal),
+                Herd::new(4, 6, 3, Orientation::Vertical),
+                // 4x Length Herds
+                Herd::new(6, 0, 4, Orientation::Horizontal),
+                // 5x Length Herds
+                Herd::new(2, 2, 5, Orientation::Vertical),
+            ],
+            vec![],
+        );
+
+        let msg = HandleMsg::Join {
             credentials: Credentials {
                 game: "foo".to_string(),
                 username: "player1".to_string(),
                 password: "1111".to_string(),
-            }, 
-            pasture:Pasture::new(
-                vec![
-                    // 2x Length Herds
-                    Herd::new(0, 0, 2, Orientation::Horizontal),
-                    // 3x Length Herds
-                    Herd::new(3, 2, 3, Orientation::Horizontal),
-                    Herd::new(4, 6, 3, Orientation::Vertical),
-                    // 4x Length Herds
-                    Herd::new(6, 0, 4, Orientation::Horizontal),
-                    // 5x Length Herds
-                    Herd::new(2, 2, 5, Orientation::Vertical),
-                ],
-                vec![]
-            )
+            },
+            commitment: commitment_digest(&player1_pasture, &player1_salt).unwrap(),
         };
 
-
-        let env = mock_env(&deps.api, "anyone", &coins(2, "token"));
+        let env = mock_env("anyone", &coins(2, "token"));
         let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
@@ -264,34 +1162,64 @@ mod tests {
         //  5x -> (5,9,Horizontal)
         
         // Other player joins
-        let msg = HandleMsg::Join { 
+        let player2_salt = Binary::from(b"player2-salt".to_vec());
+        let player2_pasture = Pasture::new(
+            vec![
+                // 2x Length Herds
+                Herd::new(6, 5, 2, Orientation::Vertical),
+                // 3x Length Herds
+                Herd::new(1, 4, 3, Orientation::Horizontal),
+                Herd::new(4, 4, 3, Orientation::Horizontal),
+                // 4x Length Herds
+                Herd::new(8, 3, 4, Orientation::Vertical),
+                // 5x Length Herds
+                Herd::new(5, 9, 5, Orientation::Horizontal),
+            ],
+            vec![],
+        );
+
+        let msg = HandleMsg::Join {
             credentials: Credentials {
                 game: "foo".to_string(),
                 username: "player2".to_string(),
                 password: "2222".to_string(),
-            }, 
-            pasture:Pasture::new(
-                vec![
-                    // 2x Length Herds
-                    Herd::new(6, 5, 2, Orientation::Vertical),
-                    // 3x Length Herds
-                    Herd::new(1, 4, 3, Orientation::Horizontal),
-                    Herd::new(4, 4, 3, Orientation::Horizontal),
-                    // 4x Length Herds
-                    Herd::new(8, 3, 4, Orientation::Vertical),
-                    // 5x Length Herds
-                    Herd::new(5, 9, 5, Orientation::Horizontal),
-                ],
-                vec![]
-            )
+            },
+            commitment: commitment_digest(&player2_pasture, &player2_salt).unwrap(),
+        };
+
+        let env = mock_env("anyone", &coins(2, "token"));
+        let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // Both players reveal their committed pastures
+        let msg = HandleMsg::Reveal {
+            credentials: Credentials {
+                game: "foo".to_string(),
+                username: "player1".to_string(),
+                password: "1111".to_string(),
+            },
+            pasture: player1_pasture,
+            salt: player1_salt,
         };
+        let env = mock_env("anyone", &coins(2, "token"));
+        let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(0, res.messages.len());
 
-        let env = mock_env(&deps.api, "anyone", &coins(2, "token"));
+        let msg = HandleMsg::Reveal {
+            credentials: Credentials {
+                game: "foo".to_string(),
+                username: "player2".to_string(),
+                password: "2222".to_string(),
+            },
+            pasture: player2_pasture,
+            salt: player2_salt,
+        };
+        let env = mock_env("anyone", &coins(2, "token"));
         let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
         // Player 1 - Query pasture created
-        let res = query(&mut deps, QueryMsg::MyPasture { 
+        let res = query(&deps, QueryMsg::MyPasture { 
             credentials: Credentials {
                 game: "foo".to_string(),
                 username: "player1".to_string(),
@@ -305,7 +1233,7 @@ mod tests {
         assert_eq!("eyJoZXJkcyI6W3siY29vcmRzIjp7IngiOjAsInkiOjB9LCJsZW5ndGgiOjIsIm9yaWVudGF0aW9uIjoiaG9yaXpvbnRhbCJ9LHsiY29vcmRzIjp7IngiOjMsInkiOjJ9LCJsZW5ndGgiOjMsIm9yaWVudGF0aW9uIjoiaG9yaXpvbnRhbCJ9LHsiY29vcmRzIjp7IngiOjQsInkiOjZ9LCJsZW5ndGgiOjMsIm9yaWVudGF0aW9uIjoidmVydGljYWwifSx7ImNvb3JkcyI6eyJ4Ijo2LCJ5IjowfSwibGVuZ3RoIjo0LCJvcmllbnRhdGlvbiI6Imhvcml6b250YWwifSx7ImNvb3JkcyI6eyJ4IjoyLCJ5IjoyfSwibGVuZ3RoIjo1LCJvcmllbnRhdGlvbiI6InZlcnRpY2FsIn1dLCJzaG90cyI6W119", res.to_base64());
 
         // Player 2 - Query pasture created
-        let res = query(&mut deps, QueryMsg::MyPasture { 
+        let res = query(&deps, QueryMsg::MyPasture {
             credentials: Credentials {
                 game: "foo".to_string(),
                 username: "player2".to_string(),
@@ -314,9 +1242,9 @@ mod tests {
         }).unwrap();
         //println!("{:?}", res.to_base64());
         /*
-            {"herds":[{"coords":{"x":0,"y":0},"length":2,"orientation":"horizontal"},{"coords":{"x":3,"y":2},"length":3,"orientation":"horizontal"},{"coords":{"x":4,"y":6},"length":3,"orientation":"vertical"},{"coords":{"x":6,"y":0},"length":4,"orientation":"horizontal"},{"coords":{"x":2,"y":2},"length":5,"orientation":"vertical"}],"shots":[]}
+            {"herds":[{"coords":{"x":6,"y":5},"length":2,"orientation":"vertical"},{"coords":{"x":1,"y":4},"length":3,"orientation":"horizontal"},{"coords":{"x":4,"y":4},"length":3,"orientation":"horizontal"},{"coords":{"x":8,"y":3},"length":4,"orientation":"vertical"},{"coords":{"x":5,"y":9},"length":5,"orientation":"horizontal"}],"shots":[]}
         */
-        assert_eq!("eyJoZXJkcyI6W3siY29vcmRzIjp7IngiOjAsInkiOjB9LCJsZW5ndGgiOjIsIm9yaWVudGF0aW9uIjoiaG9yaXpvbnRhbCJ9LHsiY29vcmRzIjp7IngiOjMsInkiOjJ9LCJsZW5ndGgiOjMsIm9yaWVudGF0aW9uIjoiaG9yaXpvbnRhbCJ9LHsiY29vcmRzIjp7IngiOjQsInkiOjZ9LCJsZW5ndGgiOjMsIm9yaWVudGF0aW9uIjoidmVydGljYWwifSx7ImNvb3JkcyI6eyJ4Ijo2LCJ5IjowfSwibGVuZ3RoIjo0LCJvcmllbnRhdGlvbiI6Imhvcml6b250YWwifSx7ImNvb3JkcyI6eyJ4IjoyLCJ5IjoyfSwibGVuZ3RoIjo1LCJvcmllbnRhdGlvbiI6InZlcnRpY2FsIn1dLCJzaG90cyI6W119", res.to_base64());
+        assert_eq!("eyJoZXJkcyI6W3siY29vcmRzIjp7IngiOjYsInkiOjV9LCJsZW5ndGgiOjIsIm9yaWVudGF0aW9uIjoidmVydGljYWwifSx7ImNvb3JkcyI6eyJ4IjoxLCJ5Ijo0fSwibGVuZ3RoIjozLCJvcmllbnRhdGlvbiI6Imhvcml6b250YWwifSx7ImNvb3JkcyI6eyJ4Ijo0LCJ5Ijo0fSwibGVuZ3RoIjozLCJvcmllbnRhdGlvbiI6Imhvcml6b250YWwifSx7ImNvb3JkcyI6eyJ4Ijo4LCJ5IjozfSwibGVuZ3RoIjo0LCJvcmllbnRhdGlvbiI6InZlcnRpY2FsIn0seyJjb29yZHMiOnsieCI6NSwieSI6OX0sImxlbmd0aCI6NSwib3JpZW50YXRpb24iOiJob3Jpem9udGFsIn1dLCJzaG90cyI6W119", res.to_base64());
 
     }
 }
\ No newline at end of file
@@ -0,0 +1,82 @@
+use cosmwasm_std::Binary;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Coords, Pasture};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Credentials {
+    pub game: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    NewGame {
+        name: String,
+        /// Seconds a player may take on their turn before the other side
+        /// can claim the game by forfeit. `None` disables the timeout.
+        turn_timeout_seconds: Option<u64>,
+    },
+    /// Creates a single-player game: the contract plays the second seat
+    /// with a hunt/target AI, so the human's board is known from the start
+    /// and needs no commit/reveal.
+    NewSoloGame {
+        name: String,
+        credentials: Credentials,
+        pasture: Pasture,
+        turn_timeout_seconds: Option<u64>,
+    },
+    Join {
+        credentials: Credentials,
+        /// sha256(serialized pasture || salt). The plaintext board is only
+        /// disclosed later, via `Reveal`.
+        commitment: Binary,
+    },
+    Shoot {
+        credentials: Credentials,
+        coords: Coords,
+    },
+    Confirm {
+        credentials: Credentials,
+        coords: Coords,
+        /// Whether the shot landed on one of the confirming player's herds.
+        /// Taken on trust until the confirming player sends `Reveal`, which
+        /// checks it against the revealed board.
+        hit: bool,
+    },
+    Reveal {
+        credentials: Credentials,
+        pasture: Pasture,
+        salt: Binary,
+    },
+    /// Claims the game by forfeit when the other side has let its turn
+    /// timeout elapse. Rejected if it is actually the claimant's own turn
+    /// or if the deadline has not passed yet.
+    ClaimTimeout {
+        credentials: Credentials,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    MyPasture { credentials: Credentials },
+    MyShots { credentials: Credentials },
+    LastShot { credentials: Credentials },
+    GameStatus { credentials: Credentials },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GameStatusResponse {
+    pub turn_username: String,
+    pub your_losses: u8,
+    pub opponent_losses: u8,
+    pub finished: bool,
+    pub winner: Option<String>,
+}
@@ -0,0 +1,645 @@
+use cosmwasm_std::{to_vec, Binary, StdError, StdResult, Storage};
+use cosmwasm_storage::{bucket, bucket_read};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::msg::Credentials;
+
+pub const BOARD_SIZE: u8 = 10;
+
+/// The required fleet: one length-2, two length-3, one length-4 and one
+/// length-5 herd, sorted so it can be compared against a pasture's herds.
+pub const FLEET_LENGTHS: [u8; 5] = [2, 3, 3, 4, 5];
+
+/// Total herd cells in a full fleet (2 + 3 + 3 + 4 + 5); once a side has
+/// taken this many confirmed hits, its whole fleet is sunk.
+pub const FLEET_CELLS: u8 = 17;
+
+pub const GAMES_KEY: &[u8] = b"games";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub struct Coords {
+    pub x: u8,
+    pub y: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Herd {
+    pub coords: Coords,
+    pub length: u8,
+    pub orientation: Orientation,
+}
+
+impl Herd {
+    pub fn new(x: u8, y: u8, length: u8, orientation: Orientation) -> Self {
+        Herd {
+            coords: Coords { x, y },
+            length,
+            orientation,
+        }
+    }
+
+    /// Every board cell this herd occupies, in order starting from `coords`.
+    pub fn cells(&self) -> Vec<Coords> {
+        (0..self.length)
+            .map(|i| match self.orientation {
+                Orientation::Horizontal => Coords {
+                    x: self.coords.x + i,
+                    y: self.coords.y,
+                },
+                Orientation::Vertical => Coords {
+                    x: self.coords.x,
+                    y: self.coords.y + i,
+                },
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+pub struct Pasture {
+    pub herds: Vec<Herd>,
+    pub shots: Vec<Coords>,
+}
+
+impl Pasture {
+    pub fn new(herds: Vec<Herd>, shots: Vec<Coords>) -> Self {
+        Pasture { herds, shots }
+    }
+
+    pub fn is_hit(&self, coords: Coords) -> bool {
+        self.herds.iter().any(|herd| herd.cells().contains(&coords))
+    }
+
+    /// Checks that the fleet is one a revealed board is allowed to contain:
+    /// every herd on the board, the right composition, and no overlaps.
+    pub fn validate(&self) -> StdResult<()> {
+        for herd in &self.herds {
+            if herd.length == 0 {
+                return Err(StdError::generic_err("herds must have a positive length"));
+            }
+            let (end_x, end_y) = match herd.orientation {
+                Orientation::Horizontal => (
+                    u16::from(herd.coords.x) + u16::from(herd.length) - 1,
+                    u16::from(herd.coords.y),
+                ),
+                Orientation::Vertical => (
+                    u16::from(herd.coords.x),
+                    u16::from(herd.coords.y) + u16::from(herd.length) - 1,
+                ),
+            };
+            if end_x >= u16::from(BOARD_SIZE) || end_y >= u16::from(BOARD_SIZE) {
+                return Err(StdError::generic_err(format!(
+                    "herd at ({}, {}) extends off the board",
+                    herd.coords.x, herd.coords.y
+                )));
+            }
+        }
+
+        let mut lengths: Vec<u8> = self.herds.iter().map(|herd| herd.length).collect();
+        lengths.sort_unstable();
+        if lengths != FLEET_LENGTHS {
+            return Err(StdError::generic_err(
+                "fleet must contain exactly one 2-length, two 3-length, one 4-length and one 5-length herd",
+            ));
+        }
+
+        let mut occupied: Vec<Coords> = vec![];
+        for herd in &self.herds {
+            for cell in herd.cells() {
+                if occupied.contains(&cell) {
+                    return Err(StdError::generic_err(format!(
+                        "herds overlap at ({}, {})",
+                        cell.x, cell.y
+                    )));
+                }
+                occupied.push(cell);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A player's board pledge: a digest over the serialized pasture and a
+/// client-chosen salt, so the board can stay hidden for the whole match and
+/// only has to be disclosed (and checked) once the game is over.
+pub fn commitment_digest(pasture: &Pasture, salt: &Binary) -> StdResult<Binary> {
+    let mut hasher = Sha256::new();
+    hasher.update(to_vec(pasture)?);
+    hasher.update(salt.as_slice());
+    Ok(Binary::from(hasher.finalize().to_vec()))
+}
+
+/// sha256(password || salt). Never store the plaintext password itself.
+pub fn hash_password(password: &str, salt: &Binary) -> Binary {
+    let mut hasher = Sha256::new();
+    hasher.update(password.as_bytes());
+    hasher.update(salt.as_slice());
+    Binary::from(hasher.finalize().to_vec())
+}
+
+/// Compares two digests in constant time, so a timing side-channel can't
+/// leak how many leading bytes of a guessed password hash matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Player {
+    pub username: String,
+    pub password_hash: Binary,
+    pub password_salt: Binary,
+    pub commitment: Binary,
+    pub revealed_pasture: Option<Pasture>,
+    pub hits_taken: u8,
+    pub ai_state: Option<AiState>,
+}
+
+impl Player {
+    pub fn new(
+        username: String,
+        password_hash: Binary,
+        password_salt: Binary,
+        commitment: Binary,
+    ) -> Self {
+        Player {
+            username,
+            password_hash,
+            password_salt,
+            commitment,
+            revealed_pasture: None,
+            hits_taken: 0,
+            ai_state: None,
+        }
+    }
+
+    /// The human side of a solo game: there is no second human to hide the
+    /// board from, so it skips commit/reveal and is known from the start.
+    pub fn new_known(
+        username: String,
+        password_hash: Binary,
+        password_salt: Binary,
+        pasture: Pasture,
+    ) -> Self {
+        Player {
+            username,
+            password_hash,
+            password_salt,
+            commitment: Binary::from(vec![]),
+            revealed_pasture: Some(pasture),
+            hits_taken: 0,
+            ai_state: None,
+        }
+    }
+
+    /// The contract-controlled opponent in a solo game. Its credentials can
+    /// never be satisfied (the password hash is empty, never a valid
+    /// digest), so no human can act on its behalf.
+    pub fn new_ai(username: String, pasture: Pasture) -> Self {
+        Player {
+            username,
+            password_hash: Binary::from(vec![]),
+            password_salt: Binary::from(vec![]),
+            commitment: Binary::from(vec![]),
+            revealed_pasture: Some(pasture),
+            hits_taken: 0,
+            ai_state: Some(AiState::new()),
+        }
+    }
+
+    pub fn matches_credentials(&self, credentials: &Credentials) -> bool {
+        self.username == credentials.username
+            && constant_time_eq(
+                hash_password(&credentials.password, &self.password_salt).as_slice(),
+                self.password_hash.as_slice(),
+            )
+    }
+
+    pub fn pasture(&self, credentials: &Credentials) -> Option<&Pasture> {
+        if self.matches_credentials(credentials) {
+            self.revealed_pasture.as_ref()
+        } else {
+            None
+        }
+    }
+}
+
+/// A shot that has already been reported on by the side that was fired at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ConfirmedShot {
+    pub target: u8,
+    pub coords: Coords,
+    pub hit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum AiMode {
+    Hunt,
+    Target,
+}
+
+/// Scratch state for the hunt/target strategy the AI opponent uses in
+/// single-player games, persisted so it survives across `handle` calls.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AiState {
+    pub mode: AiMode,
+    pub shots_fired: Vec<Coords>,
+    pub hits: Vec<Coords>,
+    pub candidate_stack: Vec<Coords>,
+    pub locked_orientation: Option<Orientation>,
+}
+
+impl AiState {
+    pub fn new() -> Self {
+        AiState {
+            mode: AiMode::Hunt,
+            shots_fired: vec![],
+            hits: vec![],
+            candidate_stack: vec![],
+            locked_orientation: None,
+        }
+    }
+
+    /// Picks the next cell to fire at, following hunt/target rules.
+    pub fn next_target(&mut self) -> Coords {
+        loop {
+            if self.mode == AiMode::Hunt {
+                break;
+            }
+            match self.candidate_stack.pop() {
+                Some(coords) if !self.shots_fired.contains(&coords) => return coords,
+                Some(_) => continue,
+                None => self.mode = AiMode::Hunt,
+            }
+        }
+
+        // Hunt mode: the smallest herd has length 2, so a checkerboard of
+        // parity `(x + y) % 2 == 0` is guaranteed to touch every herd.
+        for y in 0..BOARD_SIZE {
+            for x in 0..BOARD_SIZE {
+                let coords = Coords { x, y };
+                if (x + y) % 2 == 0 && !self.shots_fired.contains(&coords) {
+                    return coords;
+                }
+            }
+        }
+
+        // The board is exhausted; this only happens once the game is over.
+        Coords { x: 0, y: 0 }
+    }
+
+    pub fn record_result(&mut self, coords: Coords, hit: bool) {
+        self.shots_fired.push(coords);
+
+        if !hit {
+            if self.candidate_stack.is_empty() {
+                self.mode = AiMode::Hunt;
+                self.hits.clear();
+                self.locked_orientation = None;
+            }
+            return;
+        }
+
+        self.hits.push(coords);
+        self.mode = AiMode::Target;
+
+        if let Some(orientation) = self.locked_orientation.or_else(|| self.infer_orientation()) {
+            self.locked_orientation = Some(orientation);
+            self.candidate_stack.clear();
+            self.push_line_candidates(orientation);
+        } else {
+            self.push_neighbors(coords);
+        }
+    }
+
+    /// Once two hits share a row or column, the herd's orientation is known.
+    fn infer_orientation(&self) -> Option<Orientation> {
+        if self.hits.len() < 2 {
+            return None;
+        }
+        let first = self.hits[0];
+        if self.hits.iter().all(|c| c.x == first.x) {
+            return Some(Orientation::Vertical);
+        }
+        if self.hits.iter().all(|c| c.y == first.y) {
+            return Some(Orientation::Horizontal);
+        }
+        None
+    }
+
+    fn push_line_candidates(&mut self, orientation: Orientation) {
+        match orientation {
+            Orientation::Horizontal => {
+                let y = self.hits[0].y;
+                let min_x = self.hits.iter().map(|c| c.x).min().unwrap();
+                let max_x = self.hits.iter().map(|c| c.x).max().unwrap();
+                if min_x > 0 {
+                    self.push_candidate(Coords { x: min_x - 1, y });
+                }
+                if max_x < BOARD_SIZE - 1 {
+                    self.push_candidate(Coords { x: max_x + 1, y });
+                }
+            }
+            Orientation::Vertical => {
+                let x = self.hits[0].x;
+                let min_y = self.hits.iter().map(|c| c.y).min().unwrap();
+                let max_y = self.hits.iter().map(|c| c.y).max().unwrap();
+                if min_y > 0 {
+                    self.push_candidate(Coords { x, y: min_y - 1 });
+                }
+                if max_y < BOARD_SIZE - 1 {
+                    self.push_candidate(Coords { x, y: max_y + 1 });
+                }
+            }
+        }
+    }
+
+    fn push_neighbors(&mut self, coords: Coords) {
+        if coords.x > 0 {
+            self.push_candidate(Coords {
+                x: coords.x - 1,
+                y: coords.y,
+            });
+        }
+        if coords.x < BOARD_SIZE - 1 {
+            self.push_candidate(Coords {
+                x: coords.x + 1,
+                y: coords.y,
+            });
+        }
+        if coords.y > 0 {
+            self.push_candidate(Coords {
+                x: coords.x,
+                y: coords.y - 1,
+            });
+        }
+        if coords.y < BOARD_SIZE - 1 {
+            self.push_candidate(Coords {
+                x: coords.x,
+                y: coords.y + 1,
+            });
+        }
+    }
+
+    fn push_candidate(&mut self, coords: Coords) {
+        if !self.shots_fired.contains(&coords) && !self.candidate_stack.contains(&coords) {
+            self.candidate_stack.push(coords);
+        }
+    }
+}
+
+impl Default for AiState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum GameStatus {
+    InProgress,
+    Finished { winner: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Game {
+    pub name: String,
+    pub players: Vec<Player>,
+    pub turn: usize,
+    pub pending_shot: Option<Coords>,
+    pub confirmed_shots: Vec<ConfirmedShot>,
+    pub status: GameStatus,
+    pub turn_timeout_seconds: Option<u64>,
+    pub last_turn_time: u64,
+}
+
+impl Game {
+    pub fn new(name: String, turn_timeout_seconds: Option<u64>, block_time: u64) -> Self {
+        Game {
+            name,
+            players: vec![],
+            turn: 0,
+            pending_shot: None,
+            confirmed_shots: vec![],
+            status: GameStatus::InProgress,
+            turn_timeout_seconds,
+            last_turn_time: block_time,
+        }
+    }
+
+    pub fn may_load<S: Storage>(storage: &S, name: String) -> StdResult<Option<Game>> {
+        bucket_read(GAMES_KEY, storage).may_load(name.as_bytes())
+    }
+
+    pub fn load<S: Storage>(storage: &S, name: String) -> StdResult<Game> {
+        Self::may_load(storage, name.clone())?
+            .ok_or_else(|| StdError::generic_err(format!("game with name {:?} does not exist", name)))
+    }
+
+    pub fn save<S: Storage>(&self, storage: &mut S) -> StdResult<()> {
+        bucket(GAMES_KEY, storage).save(self.name.as_bytes(), self)
+    }
+
+    /// Ensures both seats are taken before any in-game action can proceed.
+    pub fn full(self) -> StdResult<Game> {
+        if self.players.len() < 2 {
+            return Err(StdError::generic_err("waiting for a second player to join"));
+        }
+        Ok(self)
+    }
+
+    pub fn add_player(&mut self, player: Player) -> StdResult<()> {
+        if self.players.len() >= 2 {
+            return Err(StdError::generic_err("this game already has two players"));
+        }
+        if self.players.iter().any(|p| p.username == player.username) {
+            return Err(StdError::generic_err(format!(
+                "username {:?} is already taken in this game",
+                player.username
+            )));
+        }
+        self.players.push(player);
+        Ok(())
+    }
+
+    /// The defender: the side that was just shot at and owes a confirmation.
+    pub fn player(&self) -> &Player {
+        &self.players[1 - self.turn]
+    }
+
+    pub fn player_mut(&mut self) -> &mut Player {
+        &mut self.players[1 - self.turn]
+    }
+
+    /// The attacker: the side whose turn it is to fire.
+    pub fn opponent(&self) -> &Player {
+        &self.players[self.turn]
+    }
+
+    pub fn shoot(&mut self, coords: Coords) {
+        self.pending_shot = Some(coords);
+    }
+
+    /// `Some(hit)` when the defender's board is already known to the
+    /// contract (a solo game's human seat, or the AI seat), so the shot can
+    /// be confirmed immediately instead of waiting on a human `Confirm`.
+    pub fn known_hit(&self, coords: Coords) -> Option<bool> {
+        self.player()
+            .revealed_pasture
+            .as_ref()
+            .map(|pasture| pasture.is_hit(coords))
+    }
+
+    /// Whether `coords` has already been fired at the current defender,
+    /// confirmed or still pending, so it can't be re-shot to rack up
+    /// repeat confirmations of the same cell.
+    pub fn already_shot(&self, coords: Coords) -> bool {
+        self.pending_shot == Some(coords)
+            || self
+                .confirmed_shots
+                .iter()
+                .any(|shot| shot.target as usize == 1 - self.turn && shot.coords == coords)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, GameStatus::Finished { .. })
+    }
+
+    /// If it's now the AI's turn to fire, picks its target, fires, and (the
+    /// AI's own board being known) resolves the hit/miss immediately. Returns
+    /// the cell the AI fired at, if any.
+    pub fn take_ai_turn_if_due(&mut self) -> Option<Coords> {
+        if self.is_finished() || self.opponent().ai_state.is_none() {
+            return None;
+        }
+
+        let ai_index = self.turn;
+        let mut ai_state = self.players[ai_index].ai_state.clone().unwrap();
+        let target = ai_state.next_target();
+
+        self.shoot(target);
+        if let Some(hit) = self.known_hit(target) {
+            ai_state.record_result(target, hit);
+            self.confirm_shot(target, hit, true);
+            self.end_turn();
+        }
+        self.players[ai_index].ai_state = Some(ai_state);
+
+        Some(target)
+    }
+
+    /// Records a shot outcome. `verified` marks whether `hit` is known to be
+    /// true (resolved against an already-revealed board, e.g. a solo game's
+    /// human seat or the AI) rather than merely claimed by a `Confirm` from
+    /// the defender: only a verified hit is allowed to end the game here,
+    /// since a self-reported one still has to survive the defender's
+    /// eventual `Reveal` before it can be trusted.
+    pub fn confirm_shot(&mut self, coords: Coords, hit: bool, verified: bool) {
+        let target = 1 - self.turn;
+        self.confirmed_shots.push(ConfirmedShot {
+            target: target as u8,
+            coords,
+            hit,
+        });
+        self.pending_shot = None;
+
+        if hit {
+            self.players[target].hits_taken += 1;
+            if verified && self.players[target].hits_taken >= FLEET_CELLS {
+                self.status = GameStatus::Finished {
+                    winner: self.players[self.turn].username.clone(),
+                };
+            }
+        }
+    }
+
+    pub fn end_turn(&mut self) {
+        self.turn = 1 - self.turn;
+    }
+
+    /// Rejects further moves once a winner has been decided.
+    pub fn ensure_in_progress(&self) -> StdResult<()> {
+        match &self.status {
+            GameStatus::InProgress => Ok(()),
+            GameStatus::Finished { winner } => Err(StdError::generic_err(format!(
+                "this game is already finished, {:?} won",
+                winner
+            ))),
+        }
+    }
+
+    /// The side that owes the next move: the defender if a shot is awaiting
+    /// confirmation, otherwise the attacker, who is due to fire.
+    pub fn next_actor(&self) -> &Player {
+        if self.pending_shot.is_some() {
+            self.player()
+        } else {
+            self.opponent()
+        }
+    }
+
+    /// Call whenever a move is made, so the timeout clock restarts.
+    pub fn touch(&mut self, block_time: u64) {
+        self.last_turn_time = block_time;
+    }
+
+    /// Lets whichever side is NOT due to move claim the game by forfeit once
+    /// the other side has missed its deadline.
+    pub fn claim_timeout(&mut self, credentials: &Credentials, block_time: u64) -> StdResult<()> {
+        self.ensure_in_progress()?;
+
+        let timeout = self
+            .turn_timeout_seconds
+            .ok_or_else(|| StdError::generic_err("this game has no turn timeout configured"))?;
+
+        let claimant = self
+            .players
+            .iter()
+            .find(|p| p.matches_credentials(credentials))
+            .ok_or_else(|| StdError::generic_err("You do not have permissions to claim this game"))?;
+
+        if self.next_actor().username == claimant.username {
+            return Err(StdError::generic_err("it is currently your turn"));
+        }
+
+        if block_time <= self.last_turn_time.saturating_add(timeout) {
+            return Err(StdError::generic_err("the turn timeout has not elapsed yet"));
+        }
+
+        self.status = GameStatus::Finished {
+            winner: claimant.username.clone(),
+        };
+        Ok(())
+    }
+
+    fn shots_fired_by(&self, index: usize) -> Vec<ConfirmedShot> {
+        self.confirmed_shots
+            .iter()
+            .filter(|shot| shot.target as usize != index)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_player_shots(&self) -> Vec<ConfirmedShot> {
+        self.shots_fired_by(1 - self.turn)
+    }
+
+    pub fn get_opponent_shots(&self) -> Vec<ConfirmedShot> {
+        self.shots_fired_by(self.turn)
+    }
+
+    pub fn next_shot(&self) -> Option<Coords> {
+        self.pending_shot
+    }
+}